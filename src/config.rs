@@ -0,0 +1,148 @@
+// Remapping profile config: `~/.config/macos_keyboard_remapper/keymaps.toml`,
+// modeled on rusty-keys' `keymap.toml`.
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::{env, fs};
+
+use serde::Deserialize;
+
+use crate::keycodes::keycode_for_name;
+
+/// The right-hand side of a `mappings` entry: either a single key (`quote = "q"`) or a
+/// sequence to type out in order (`f1 = ["a", "b", "c"]`) for macro-style expansion.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum MappingTarget {
+    Key(String),
+    Sequence(Vec<String>),
+}
+
+/// One named remapping profile, e.g. `dvorak-to-qwerty`.
+#[derive(Debug, Deserialize)]
+pub struct KeymapConfig {
+    pub name: String,
+    /// Input source IDs (as reported by `kTISPropertyInputSourceID`) this keymap applies to.
+    #[serde(default)]
+    pub input_sources: Vec<String>,
+    /// Symbolic `from_key = "to_key"` entries, e.g. `quote = "q"`.
+    #[serde(default)]
+    pub mappings: HashMap<String, MappingTarget>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Config {
+    pub active_keymap: String,
+    pub keymaps: Vec<KeymapConfig>,
+    /// Keycode that cycles to the next keymap when pressed (works for the left/right
+    /// modifier keys, e.g. Right-Option, as well as regular ones; CapsLock and Function are
+    /// swallowed but never switch). `None` disables switching.
+    #[serde(default)]
+    pub switch_trigger_keycode: Option<u64>,
+}
+
+// Built-in fallback, equivalent to the remapper's original hardcoded Dvorak table.
+const DEFAULT_KEYMAPS_TOML: &str = r#"
+active_keymap = "dvorak-to-qwerty"
+
+[[keymaps]]
+name = "dvorak-to-qwerty"
+input_sources = ["com.apple.keylayout.DVORAK-QWERTYCMD"]
+
+[keymaps.mappings]
+quote = "q"
+comma = "w"
+period = "e"
+p = "r"
+y = "t"
+f = "y"
+g = "u"
+c = "i"
+r = "o"
+l = "p"
+slash = "leftbracket"
+equals = "rightbracket"
+o = "s"
+e = "d"
+u = "f"
+i = "g"
+d = "h"
+h = "j"
+t = "k"
+n = "l"
+s = "semicolon"
+minus = "quote"
+semicolon = "z"
+q = "x"
+j = "c"
+k = "v"
+x = "b"
+b = "n"
+w = "comma"
+v = "period"
+z = "slash"
+leftbracket = "minus"
+rightbracket = "equals"
+
+[[keymaps]]
+name = "passthrough"
+mappings = {}
+"#;
+
+fn config_path() -> Option<PathBuf> {
+    let home = env::var("HOME").ok()?;
+    Some(PathBuf::from(home).join(".config/macos_keyboard_remapper/keymaps.toml"))
+}
+
+/// Load the keymap configuration, falling back to the built-in Dvorak-to-QWERTY table
+/// if no user config file exists (or it fails to parse).
+pub fn load_config() -> Config {
+    let toml_str = config_path()
+        .and_then(|path| fs::read_to_string(&path).ok())
+        .unwrap_or_else(|| DEFAULT_KEYMAPS_TOML.to_string());
+
+    toml::from_str(&toml_str).unwrap_or_else(|e| {
+        tracing::error!(%e, "Failed to parse keymaps.toml, falling back to built-in Dvorak table");
+        toml::from_str(DEFAULT_KEYMAPS_TOML).expect("built-in keymap TOML is valid")
+    })
+}
+
+/// What a resolved `mappings` entry does to the key that triggers it.
+#[derive(Debug)]
+pub enum Remap {
+    /// Rewrite the event's keycode in place.
+    Key(u64),
+    /// Drop the triggering event and synthesize this sequence of keystrokes instead.
+    Sequence(Vec<u64>),
+}
+
+/// Resolve a keymap's symbolic `mappings` table into a keycode -> `Remap` lookup.
+pub fn resolve_keymap(keymap: &KeymapConfig) -> HashMap<u64, Remap> {
+    let mut resolved = HashMap::new();
+    for (from, to) in &keymap.mappings {
+        let Some(from_code) = keycode_for_name(from) else {
+            tracing::warn!(from, "Unknown key name in keymaps.toml, skipping");
+            continue;
+        };
+        let remap = match to {
+            MappingTarget::Key(name) => match keycode_for_name(name) {
+                Some(to_code) => Remap::Key(to_code),
+                None => {
+                    tracing::warn!(from, to = name, "Unknown key name in keymaps.toml, skipping");
+                    continue;
+                }
+            },
+            MappingTarget::Sequence(names) => {
+                let codes: Option<Vec<u64>> = names.iter().map(|n| keycode_for_name(n)).collect();
+                match codes {
+                    Some(codes) => Remap::Sequence(codes),
+                    None => {
+                        tracing::warn!(from, ?names, "Unknown key name in macro sequence, skipping");
+                        continue;
+                    }
+                }
+            }
+        };
+        resolved.insert(from_code, remap);
+    }
+    resolved
+}
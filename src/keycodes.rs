@@ -0,0 +1,328 @@
+use core_graphics::event::CGEventFlags;
+
+// Key code constants (from HIToolbox/Events.h, kVK_*):
+pub const VK_A: u64 = 0;
+pub const VK_S: u64 = 1;
+pub const VK_D: u64 = 2;
+pub const VK_F: u64 = 3;
+pub const VK_H: u64 = 4;
+pub const VK_G: u64 = 5;
+pub const VK_Z: u64 = 6;
+pub const VK_X: u64 = 7;
+pub const VK_C: u64 = 8;
+pub const VK_V: u64 = 9;
+pub const VK_B: u64 = 11;
+pub const VK_Q: u64 = 12;
+pub const VK_W: u64 = 13;
+pub const VK_E: u64 = 14;
+pub const VK_R: u64 = 15;
+pub const VK_Y: u64 = 16;
+pub const VK_T: u64 = 17;
+pub const VK_ANSI_1: u64 = 18;
+pub const VK_ANSI_2: u64 = 19;
+pub const VK_ANSI_3: u64 = 20;
+pub const VK_ANSI_4: u64 = 21;
+pub const VK_ANSI_6: u64 = 22;
+pub const VK_ANSI_5: u64 = 23;
+pub const VK_ANSI_EQUALS: u64 = 24;
+pub const VK_ANSI_9: u64 = 25;
+pub const VK_ANSI_7: u64 = 26;
+pub const VK_MINUS: u64 = 27;
+pub const VK_ANSI_8: u64 = 28;
+pub const VK_ANSI_0: u64 = 29;
+pub const VK_RIGHTBRACKET: u64 = 30;
+pub const VK_O: u64 = 31;
+pub const VK_U: u64 = 32;
+pub const VK_LEFTBRACKET: u64 = 33;
+pub const VK_I: u64 = 34;
+pub const VK_P: u64 = 35;
+pub const VK_L: u64 = 37;
+pub const VK_J: u64 = 38;
+pub const VK_QUOTE: u64 = 39;
+pub const VK_K: u64 = 40;
+pub const VK_SEMICOLON: u64 = 41;
+pub const VK_BACKSLASH: u64 = 42;
+pub const VK_COMMA: u64 = 43;
+pub const VK_SLASH: u64 = 44;
+pub const VK_N: u64 = 45;
+pub const VK_M: u64 = 46;
+pub const VK_PERIOD: u64 = 47;
+
+// Modifier keys, reported via FlagsChanged rather than KeyDown/KeyUp.
+pub const VK_RIGHT_COMMAND: u64 = 54;
+pub const VK_COMMAND: u64 = 55;
+pub const VK_SHIFT: u64 = 56;
+pub const VK_CAPSLOCK: u64 = 57;
+pub const VK_OPTION: u64 = 58;
+pub const VK_CONTROL: u64 = 59;
+pub const VK_RIGHT_SHIFT: u64 = 60;
+pub const VK_RIGHT_OPTION: u64 = 61;
+pub const VK_RIGHT_CONTROL: u64 = 62;
+pub const VK_FUNCTION: u64 = 63;
+
+// Whitespace/editing keys.
+pub const VK_RETURN: u64 = 36;
+pub const VK_TAB: u64 = 48;
+pub const VK_SPACE: u64 = 49;
+pub const VK_GRAVE: u64 = 50;
+pub const VK_DELETE: u64 = 51;
+pub const VK_ESCAPE: u64 = 53;
+pub const VK_FORWARD_DELETE: u64 = 117;
+pub const VK_HELP: u64 = 114;
+pub const VK_HOME: u64 = 115;
+pub const VK_END: u64 = 119;
+pub const VK_PAGE_UP: u64 = 116;
+pub const VK_PAGE_DOWN: u64 = 121;
+
+// Arrow keys.
+pub const VK_LEFT_ARROW: u64 = 123;
+pub const VK_RIGHT_ARROW: u64 = 124;
+pub const VK_DOWN_ARROW: u64 = 125;
+pub const VK_UP_ARROW: u64 = 126;
+
+// Function keys.
+pub const VK_F1: u64 = 122;
+pub const VK_F2: u64 = 120;
+pub const VK_F3: u64 = 99;
+pub const VK_F4: u64 = 118;
+pub const VK_F5: u64 = 96;
+pub const VK_F6: u64 = 97;
+pub const VK_F7: u64 = 98;
+pub const VK_F8: u64 = 100;
+pub const VK_F9: u64 = 101;
+pub const VK_F10: u64 = 109;
+pub const VK_F11: u64 = 103;
+pub const VK_F12: u64 = 111;
+pub const VK_F13: u64 = 105;
+pub const VK_F14: u64 = 107;
+pub const VK_F15: u64 = 113;
+pub const VK_F16: u64 = 106;
+pub const VK_F17: u64 = 64;
+pub const VK_F18: u64 = 79;
+pub const VK_F19: u64 = 80;
+pub const VK_F20: u64 = 90;
+
+// Media/volume keys.
+pub const VK_VOLUME_UP: u64 = 72;
+pub const VK_VOLUME_DOWN: u64 = 73;
+pub const VK_MUTE: u64 = 74;
+
+// Keypad.
+pub const VK_KEYPAD_DECIMAL: u64 = 65;
+pub const VK_KEYPAD_MULTIPLY: u64 = 67;
+pub const VK_KEYPAD_PLUS: u64 = 69;
+pub const VK_KEYPAD_CLEAR: u64 = 71;
+pub const VK_KEYPAD_DIVIDE: u64 = 75;
+pub const VK_KEYPAD_ENTER: u64 = 76;
+pub const VK_KEYPAD_MINUS: u64 = 78;
+pub const VK_KEYPAD_EQUALS: u64 = 81;
+pub const VK_KEYPAD_0: u64 = 82;
+pub const VK_KEYPAD_1: u64 = 83;
+pub const VK_KEYPAD_2: u64 = 84;
+pub const VK_KEYPAD_3: u64 = 85;
+pub const VK_KEYPAD_4: u64 = 86;
+pub const VK_KEYPAD_5: u64 = 87;
+pub const VK_KEYPAD_6: u64 = 88;
+pub const VK_KEYPAD_7: u64 = 89;
+pub const VK_KEYPAD_8: u64 = 91;
+pub const VK_KEYPAD_9: u64 = 92;
+
+/// Symbolic name <-> keycode table used both by the config parser (`keycode_for_name`) and
+/// the `dump-keys` diagnostic (`name_for_keycode`). Covers the ranges 0-53, 54-63 and 64-126
+/// from HIToolbox/Events.h: the alphanumeric block, modifiers, function keys, arrows, keypad,
+/// and media keys.
+const KEY_NAMES: &[(&str, u64)] = &[
+    ("a", VK_A),
+    ("s", VK_S),
+    ("d", VK_D),
+    ("f", VK_F),
+    ("h", VK_H),
+    ("g", VK_G),
+    ("z", VK_Z),
+    ("x", VK_X),
+    ("c", VK_C),
+    ("v", VK_V),
+    ("b", VK_B),
+    ("q", VK_Q),
+    ("w", VK_W),
+    ("e", VK_E),
+    ("r", VK_R),
+    ("y", VK_Y),
+    ("t", VK_T),
+    ("1", VK_ANSI_1),
+    ("2", VK_ANSI_2),
+    ("3", VK_ANSI_3),
+    ("4", VK_ANSI_4),
+    ("6", VK_ANSI_6),
+    ("5", VK_ANSI_5),
+    ("equals", VK_ANSI_EQUALS),
+    ("9", VK_ANSI_9),
+    ("7", VK_ANSI_7),
+    ("minus", VK_MINUS),
+    ("8", VK_ANSI_8),
+    ("0", VK_ANSI_0),
+    ("rightbracket", VK_RIGHTBRACKET),
+    ("o", VK_O),
+    ("u", VK_U),
+    ("leftbracket", VK_LEFTBRACKET),
+    ("i", VK_I),
+    ("p", VK_P),
+    ("l", VK_L),
+    ("j", VK_J),
+    ("quote", VK_QUOTE),
+    ("k", VK_K),
+    ("semicolon", VK_SEMICOLON),
+    ("backslash", VK_BACKSLASH),
+    ("comma", VK_COMMA),
+    ("slash", VK_SLASH),
+    ("n", VK_N),
+    ("m", VK_M),
+    ("period", VK_PERIOD),
+    ("rightcommand", VK_RIGHT_COMMAND),
+    ("command", VK_COMMAND),
+    ("shift", VK_SHIFT),
+    ("capslock", VK_CAPSLOCK),
+    ("option", VK_OPTION),
+    ("control", VK_CONTROL),
+    ("rightshift", VK_RIGHT_SHIFT),
+    ("rightoption", VK_RIGHT_OPTION),
+    ("rightcontrol", VK_RIGHT_CONTROL),
+    ("function", VK_FUNCTION),
+    ("return", VK_RETURN),
+    ("tab", VK_TAB),
+    ("space", VK_SPACE),
+    ("grave", VK_GRAVE),
+    ("delete", VK_DELETE),
+    ("escape", VK_ESCAPE),
+    ("forwarddelete", VK_FORWARD_DELETE),
+    ("help", VK_HELP),
+    ("home", VK_HOME),
+    ("end", VK_END),
+    ("pageup", VK_PAGE_UP),
+    ("pagedown", VK_PAGE_DOWN),
+    ("leftarrow", VK_LEFT_ARROW),
+    ("rightarrow", VK_RIGHT_ARROW),
+    ("downarrow", VK_DOWN_ARROW),
+    ("uparrow", VK_UP_ARROW),
+    ("f1", VK_F1),
+    ("f2", VK_F2),
+    ("f3", VK_F3),
+    ("f4", VK_F4),
+    ("f5", VK_F5),
+    ("f6", VK_F6),
+    ("f7", VK_F7),
+    ("f8", VK_F8),
+    ("f9", VK_F9),
+    ("f10", VK_F10),
+    ("f11", VK_F11),
+    ("f12", VK_F12),
+    ("f13", VK_F13),
+    ("f14", VK_F14),
+    ("f15", VK_F15),
+    ("f16", VK_F16),
+    ("f17", VK_F17),
+    ("f18", VK_F18),
+    ("f19", VK_F19),
+    ("f20", VK_F20),
+    ("volumeup", VK_VOLUME_UP),
+    ("volumedown", VK_VOLUME_DOWN),
+    ("mute", VK_MUTE),
+    ("keypaddecimal", VK_KEYPAD_DECIMAL),
+    ("keypadmultiply", VK_KEYPAD_MULTIPLY),
+    ("keypadplus", VK_KEYPAD_PLUS),
+    ("keypadclear", VK_KEYPAD_CLEAR),
+    ("keypaddivide", VK_KEYPAD_DIVIDE),
+    ("keypadenter", VK_KEYPAD_ENTER),
+    ("keypadminus", VK_KEYPAD_MINUS),
+    ("keypadequals", VK_KEYPAD_EQUALS),
+    ("keypad0", VK_KEYPAD_0),
+    ("keypad1", VK_KEYPAD_1),
+    ("keypad2", VK_KEYPAD_2),
+    ("keypad3", VK_KEYPAD_3),
+    ("keypad4", VK_KEYPAD_4),
+    ("keypad5", VK_KEYPAD_5),
+    ("keypad6", VK_KEYPAD_6),
+    ("keypad7", VK_KEYPAD_7),
+    ("keypad8", VK_KEYPAD_8),
+    ("keypad9", VK_KEYPAD_9),
+];
+
+/// Look up a keycode by the symbolic name used in `keymaps.toml` (e.g. `"quote"`, `"a"`).
+pub fn keycode_for_name(name: &str) -> Option<u64> {
+    KEY_NAMES.iter().find(|(n, _)| *n == name).map(|(_, c)| *c)
+}
+
+/// Look up the symbolic name for a keycode, for the `dump-keys` diagnostic.
+pub fn name_for_keycode(code: u64) -> Option<&'static str> {
+    KEY_NAMES.iter().find(|(_, c)| *c == code).map(|(n, _)| *n)
+}
+
+/// Device-dependent modifier flag bits carried on `FlagsChanged` events, not exposed by
+/// `CGEventFlags`. Used to tell which physical side of a paired modifier changed, and
+/// whether it went down (bit set) or up (bit cleared).
+pub const DEVICE_LCTL_MASK: u64 = 0x0000_0001;
+pub const DEVICE_LSHIFT_MASK: u64 = 0x0000_0002;
+pub const DEVICE_RSHIFT_MASK: u64 = 0x0000_0004;
+pub const DEVICE_LCMD_MASK: u64 = 0x0000_0008;
+pub const DEVICE_RCMD_MASK: u64 = 0x0000_0010;
+pub const DEVICE_LALT_MASK: u64 = 0x0000_0020;
+pub const DEVICE_RALT_MASK: u64 = 0x0000_0040;
+pub const DEVICE_RCTL_MASK: u64 = 0x0000_2000;
+
+/// Device mask bit that reflects whether this modifier keycode is currently held, if it's
+/// one of the paired left/right modifiers that FlagsChanged carries such a bit for.
+///
+/// `VK_CAPSLOCK` and `VK_FUNCTION` are deliberately absent: CapsLock only ever reports its
+/// sticky toggle state (not a momentary press/release) and Function has no device mask at
+/// all, so neither can be driven through this press/release model. See
+/// `is_modifier_keycode` for recognizing them anyway, so a remap naming one can be rejected
+/// with a clear warning instead of silently doing nothing.
+pub fn device_mask_for_modifier(keycode: u64) -> Option<u64> {
+    Some(match keycode {
+        VK_CONTROL => DEVICE_LCTL_MASK,
+        VK_RIGHT_CONTROL => DEVICE_RCTL_MASK,
+        VK_SHIFT => DEVICE_LSHIFT_MASK,
+        VK_RIGHT_SHIFT => DEVICE_RSHIFT_MASK,
+        VK_COMMAND => DEVICE_LCMD_MASK,
+        VK_RIGHT_COMMAND => DEVICE_RCMD_MASK,
+        VK_OPTION => DEVICE_LALT_MASK,
+        VK_RIGHT_OPTION => DEVICE_RALT_MASK,
+        _ => return None,
+    })
+}
+
+/// True for any key that's reported via `FlagsChanged` rather than `KeyDown`/`KeyUp`,
+/// including `VK_CAPSLOCK` and `VK_FUNCTION` which `device_mask_for_modifier` can't drive.
+pub fn is_modifier_keycode(keycode: u64) -> bool {
+    (VK_RIGHT_COMMAND..=VK_FUNCTION).contains(&keycode)
+}
+
+/// Canonical `kCGEventFlagMask*` bit for this modifier's family — what apps (and the
+/// remap gate in `main.rs`) actually check, as opposed to the device-dependent left/right
+/// bits above, which `CGEventFlags::from_bits_truncate` silently discards on write.
+pub fn canonical_flag_for_modifier(keycode: u64) -> Option<CGEventFlags> {
+    Some(match keycode {
+        VK_CONTROL | VK_RIGHT_CONTROL => CGEventFlags::CGEventFlagControl,
+        VK_SHIFT | VK_RIGHT_SHIFT => CGEventFlags::CGEventFlagShift,
+        VK_COMMAND | VK_RIGHT_COMMAND => CGEventFlags::CGEventFlagCommand,
+        VK_OPTION | VK_RIGHT_OPTION => CGEventFlags::CGEventFlagAlternate,
+        _ => return None,
+    })
+}
+
+/// The device mask bit for the *other* side of `keycode`'s left/right pair. Both sides of
+/// a pair share one canonical flag bit, so this tells a caller whether it's still safe to
+/// clear that canonical bit or whether the sibling key is holding it up.
+pub fn sibling_device_mask(keycode: u64) -> Option<u64> {
+    Some(match keycode {
+        VK_CONTROL => DEVICE_RCTL_MASK,
+        VK_RIGHT_CONTROL => DEVICE_LCTL_MASK,
+        VK_SHIFT => DEVICE_RSHIFT_MASK,
+        VK_RIGHT_SHIFT => DEVICE_LSHIFT_MASK,
+        VK_COMMAND => DEVICE_RCMD_MASK,
+        VK_RIGHT_COMMAND => DEVICE_LCMD_MASK,
+        VK_OPTION => DEVICE_RALT_MASK,
+        VK_RIGHT_OPTION => DEVICE_LALT_MASK,
+        _ => return None,
+    })
+}
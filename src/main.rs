@@ -1,23 +1,36 @@
-// A macOS keyboard remapper from Dvorak to QWERTY when Command, Control, or Function keys are pressed.
+// A macOS keyboard remapper, configured by a TOML file of named keymaps.
 use clap::{Parser, Subcommand};
 use core_foundation::runloop::{CFRunLoop, kCFRunLoopCommonModes};
 use core_foundation::string::CFStringRef;
 use core_graphics::event::{
-    CGEventFlags, CGEventTap, CGEventTapLocation, CGEventTapOptions, CGEventTapPlacement,
-    CGEventType, EventField,
+    CGEvent, CGEventFlags, CGEventTap, CGEventTapLocation, CGEventTapOptions,
+    CGEventTapPlacement, CGEventType, EventField,
 };
+use core_graphics::event_source::{CGEventSource, CGEventSourceStateID};
+use std::collections::HashMap;
 use std::error::Error;
 use std::os::raw::c_void;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
 use std::{env, fs, process};
 use tracing::{debug, error, info, warn};
 use tracing_subscriber::EnvFilter;
 
+mod config;
+mod keycodes;
+
 // Command-line interface
 #[derive(Parser)]
 #[command(
     name = "macos_keyboard_remapper",
     version,
-    about = "Remap Dvorak to QWERTY on macOS"
+    about = "Remap keys on macOS using a configurable keymaps.toml",
+    long_about = "Remap keys on macOS using a configurable keymaps.toml.\n\n\
+                  Note: CapsLock and Function can't be the source or destination of a \
+                  FlagsChanged modifier remap (e.g. `capslock = \"control\"`) — unlike the \
+                  other modifiers, they only ever report a sticky toggle state rather than \
+                  momentary press/release, so this is unimplemented. Such an entry is left \
+                  unremapped at runtime with a warning logged, not silently applied."
 )]
 struct Cli {
     #[command(subcommand)]
@@ -30,56 +43,10 @@ enum Commands {
     Install,
     /// Remove the LaunchAgent
     Uninstall,
+    /// Log each keypress as its symbolic name and raw keycode, without remapping
+    DumpKeys,
 }
-// Key code constants (from HIToolbox/Events.h, kVK_*):
-const VK_A: u64 = 0;
-const VK_S: u64 = 1;
-const VK_D: u64 = 2;
-const VK_F: u64 = 3;
-const VK_H: u64 = 4;
-const VK_G: u64 = 5;
-const VK_Z: u64 = 6;
-const VK_X: u64 = 7;
-const VK_C: u64 = 8;
-const VK_V: u64 = 9;
-const VK_B: u64 = 11;
-const VK_Q: u64 = 12;
-const VK_W: u64 = 13;
-const VK_E: u64 = 14;
-const VK_R: u64 = 15;
-const VK_Y: u64 = 16;
-const VK_T: u64 = 17;
-const VK_ANSI_1: u64 = 18;
-const VK_ANSI_2: u64 = 19;
-const VK_ANSI_3: u64 = 20;
-const VK_ANSI_4: u64 = 21;
-const VK_ANSI_6: u64 = 22;
-const VK_ANSI_5: u64 = 23;
-const VK_ANSI_EQUALS: u64 = 24;
-const VK_ANSI_9: u64 = 25;
-const VK_ANSI_7: u64 = 26;
-const VK_MINUS: u64 = 27;
-const VK_ANSI_8: u64 = 28;
-const VK_ANSI_0: u64 = 29;
-const VK_RIGHTBRACKET: u64 = 30;
-const VK_O: u64 = 31;
-const VK_U: u64 = 32;
-const VK_LEFTBRACKET: u64 = 33;
-const VK_I: u64 = 34;
-const VK_P: u64 = 35;
-const VK_L: u64 = 37;
-const VK_J: u64 = 38;
-const VK_QUOTE: u64 = 39;
-const VK_K: u64 = 40;
-const VK_SEMICOLON: u64 = 41;
-const VK_BACKSLASH: u64 = 42;
-const VK_COMMA: u64 = 43;
-const VK_SLASH: u64 = 44;
-const VK_N: u64 = 45;
-const VK_M: u64 = 46;
-const VK_PERIOD: u64 = 47;
-
-// Text input source detection (to only remap on Dvorak)
+// Text input source detection (to only remap when the active keymap's source is current)
 type TISInputSourceRef = *mut c_void;
 #[link(name = "Carbon", kind = "framework")]
 unsafe extern "C" {
@@ -105,17 +72,80 @@ unsafe extern "C" {
 use std::ffi::CStr;
 const K_CFSTRING_ENCODING_UTF8: u32 = 0x08000100;
 
-fn is_dvorak_name(s: &[u8]) -> bool {
-    if s == "com.apple.keylayout.DVORAK-QWERTYCMD".as_bytes() {
-        true
-    } else {
-        debug!("the layout is actually {:?}", str::from_utf8(s));
-        false
+/// One configured remapping profile, resolved from config at startup.
+struct Profile {
+    name: String,
+    /// Input source IDs this keymap should apply to (empty = always active).
+    input_sources: Vec<String>,
+    mapping: HashMap<u64, config::Remap>,
+}
+
+/// All configured profiles, plus which one is live right now.
+struct Keymaps {
+    profiles: Vec<Profile>,
+    current: AtomicUsize,
+    /// Raw keycode that cycles `current` to the next profile. `None` disables switching.
+    switch_trigger_keycode: Option<u64>,
+}
+
+static KEYMAPS: OnceLock<Keymaps> = OnceLock::new();
+
+fn keymaps() -> &'static Keymaps {
+    KEYMAPS.get().expect("keymaps not initialized")
+}
+
+fn active_profile() -> &'static Profile {
+    let keymaps = keymaps();
+    &keymaps.profiles[keymaps.current.load(Ordering::Relaxed)]
+}
+
+/// Switch to the next configured profile, wrapping around, and log the change.
+fn switch_to_next_profile() {
+    let keymaps = keymaps();
+    let next = (keymaps.current.load(Ordering::Relaxed) + 1) % keymaps.profiles.len();
+    keymaps.current.store(next, Ordering::Relaxed);
+    info!(keymap = %keymaps.profiles[next].name, "Switched active keymap");
+}
+
+/// Load `keymaps.toml` and resolve every configured keymap into a keycode lookup.
+fn init_keymaps() {
+    let config = config::load_config();
+    let profiles: Vec<Profile> = config
+        .keymaps
+        .iter()
+        .map(|k| Profile {
+            name: k.name.clone(),
+            input_sources: k.input_sources.clone(),
+            mapping: config::resolve_keymap(k),
+        })
+        .collect();
+    let current = profiles
+        .iter()
+        .position(|p| p.name == config.active_keymap)
+        .unwrap_or_else(|| {
+            error!(
+                keymap = %config.active_keymap,
+                "active_keymap not found among configured keymaps; exiting"
+            );
+            process::exit(1);
+        });
+    let _ = KEYMAPS.set(Keymaps {
+        profiles,
+        current: AtomicUsize::new(current),
+        switch_trigger_keycode: config.switch_trigger_keycode,
+    });
+}
+
+fn is_source_name_active(s: &[u8]) -> bool {
+    let sources = &active_profile().input_sources;
+    if sources.is_empty() {
+        return true;
     }
+    sources.iter().any(|src| src.as_bytes() == s)
 }
 
-/// Returns true if current keyboard layout is Dvorak
-fn is_dvorak() -> bool {
+/// Returns true if the current keyboard layout is one the active keymap applies to
+fn should_remap_in_active_source() -> bool {
     unsafe {
         let src = TISCopyCurrentKeyboardLayoutInputSource();
         if src.is_null() {
@@ -126,8 +156,8 @@ fn is_dvorak() -> bool {
         let id_cf = TISGetInputSourceProperty(src, kTISPropertyInputSourceID);
         let ptr = CFStringGetCStringPtr(id_cf, K_CFSTRING_ENCODING_UTF8);
 
-        let is_dvorak = if !ptr.is_null() {
-            is_dvorak_name(CStr::from_ptr(ptr).to_bytes())
+        let is_active = if !ptr.is_null() {
+            is_source_name_active(CStr::from_ptr(ptr).to_bytes())
         } else {
             let mut buf = [0i8; 256];
             if CFStringGetCString(
@@ -136,7 +166,7 @@ fn is_dvorak() -> bool {
                 buf.len() as isize,
                 K_CFSTRING_ENCODING_UTF8,
             ) {
-                is_dvorak_name(CStr::from_ptr(buf.as_ptr()).to_bytes())
+                is_source_name_active(CStr::from_ptr(buf.as_ptr()).to_bytes())
             } else {
                 false
             }
@@ -144,60 +174,49 @@ fn is_dvorak() -> bool {
 
         CFRelease(src as *const c_void);
 
-        is_dvorak
+        is_active
     }
 }
 
-// Remap Dvorak keycodes to QWERTY keycodes (only when on Dvorak layout)
-fn remap_key(key: u64) -> Option<u64> {
-    if !is_dvorak() {
+// Look up how the active keymap remaps a keycode (only when its input source is current)
+fn lookup_remap(key: u64) -> Option<&'static config::Remap> {
+    if !should_remap_in_active_source() {
         return None;
     }
-    match key {
-        VK_QUOTE => Some(VK_Q),
-        VK_COMMA => Some(VK_W),
-        VK_PERIOD => Some(VK_E),
-        VK_P => Some(VK_R),
-        VK_Y => Some(VK_T),
-        VK_F => Some(VK_Y),
-        VK_G => Some(VK_U),
-        VK_C => Some(VK_I),
-        VK_R => Some(VK_O),
-        VK_L => Some(VK_P),
-        VK_SLASH => Some(VK_LEFTBRACKET),
-        VK_ANSI_EQUALS => Some(VK_RIGHTBRACKET),
-
-        VK_A => Some(VK_A),
-        VK_O => Some(VK_S),
-        VK_E => Some(VK_D),
-        VK_U => Some(VK_F),
-        VK_I => Some(VK_G),
-        VK_D => Some(VK_H),
-        VK_H => Some(VK_J),
-        VK_T => Some(VK_K),
-        VK_N => Some(VK_L),
-        VK_S => Some(VK_SEMICOLON),
-        VK_MINUS => Some(VK_QUOTE),
-
-        VK_SEMICOLON => Some(VK_Z),
-        VK_Q => Some(VK_X),
-        VK_J => Some(VK_C),
-        VK_K => Some(VK_V),
-        VK_X => Some(VK_B),
-        VK_B => Some(VK_N),
-        VK_M => Some(VK_M),
-        VK_W => Some(VK_COMMA),
-        VK_V => Some(VK_PERIOD),
-        VK_Z => Some(VK_SLASH),
-        VK_BACKSLASH => Some(VK_BACKSLASH),
-
-        VK_LEFTBRACKET => Some(VK_MINUS),
-        VK_RIGHTBRACKET => Some(VK_ANSI_EQUALS),
+    active_profile().mapping.get(&key)
+}
 
-        VK_ANSI_1 | VK_ANSI_2 | VK_ANSI_3 | VK_ANSI_4 | VK_ANSI_5 | VK_ANSI_6 | VK_ANSI_7
-        | VK_ANSI_8 | VK_ANSI_9 | VK_ANSI_0 => Some(key),
+/// Sentinel stamped on synthetic events (via `EVENT_SOURCE_USER_DATA`) so the tap callback
+/// recognizes and ignores its own injected keystrokes instead of remapping them again.
+const SYNTHETIC_EVENT_USER_DATA: i64 = 0x4D4B_5231; // "MKR1"
 
-        _ => None,
+/// Post a macro sequence of keydown/keyup pairs via a fresh `CGEventSource`.
+fn post_sequence(keys: &[u64]) {
+    let source = match CGEventSource::new(CGEventSourceStateID::HIDSystemState) {
+        Ok(source) => source,
+        Err(()) => {
+            error!("Failed to create CGEventSource for macro expansion");
+            return;
+        }
+    };
+    for &key in keys {
+        for key_down in [true, false] {
+            match CGEvent::new_keyboard_event(&source, key as u16, key_down) {
+                Ok(event) => {
+                    // `HIDSystemState` reflects live hardware modifier state, and the
+                    // Control/Fn key that gated this macro is typically still physically
+                    // held when we post — without this, the expansion would inherit that
+                    // modifier (e.g. Ctrl+a instead of a) instead of typing literal keys.
+                    event.set_flags(CGEventFlags::empty());
+                    event.set_integer_value_field(
+                        EventField::EVENT_SOURCE_USER_DATA,
+                        SYNTHETIC_EVENT_USER_DATA,
+                    );
+                    event.post(CGEventTapLocation::AnnotatedSession);
+                }
+                Err(()) => error!(key, "Failed to synthesize keyboard event"),
+            }
+        }
     }
 }
 
@@ -222,7 +241,11 @@ fn main() {
                 process::exit(1);
             }
         }
+        Some(Commands::DumpKeys) => {
+            run_dump_keys();
+        }
         None => {
+            init_keymaps();
             run_tap();
         }
     }
@@ -305,30 +328,151 @@ fn run_tap() -> ! {
         vec![
             CGEventType::KeyDown,
             CGEventType::KeyUp,
+            CGEventType::FlagsChanged,
             CGEventType::TapDisabledByTimeout,
             CGEventType::TapDisabledByUserInput,
         ],
         |_, event_type, event| {
+            if event.get_integer_value_field(EventField::EVENT_SOURCE_USER_DATA)
+                == SYNTHETIC_EVENT_USER_DATA
+            {
+                // Our own injected keystroke from a macro expansion; don't reprocess it.
+                return None;
+            }
             match event_type {
                 CGEventType::KeyDown | CGEventType::KeyUp => {
                     let keycode =
                         event.get_integer_value_field(EventField::KEYBOARD_EVENT_KEYCODE) as u64;
+                    if keymaps().switch_trigger_keycode == Some(keycode) {
+                        if event_type == CGEventType::KeyDown {
+                            switch_to_next_profile();
+                        }
+                        // Swallow the trigger keypress itself: a passed-through `None` would
+                        // forward the original event unchanged, not drop it.
+                        event.set_type(CGEventType::Null);
+                        return Some(event.clone());
+                    }
                     if !(event.get_flags()
                         & (CGEventFlags::CGEventFlagControl | CGEventFlags::CGEventFlagSecondaryFn))
                         .is_empty()
                     {
-                        if let Some(mapped) = remap_key(keycode) {
-                            debug!("Remapped {} to {}", keycode, mapped);
-                            event.set_integer_value_field(
-                                EventField::KEYBOARD_EVENT_KEYCODE,
-                                mapped as i64,
-                            );
-                            return Some(event.clone());
+                        match lookup_remap(keycode) {
+                            Some(config::Remap::Key(mapped)) => {
+                                debug!("Remapped {} to {}", keycode, mapped);
+                                event.set_integer_value_field(
+                                    EventField::KEYBOARD_EVENT_KEYCODE,
+                                    *mapped as i64,
+                                );
+                                return Some(event.clone());
+                            }
+                            Some(config::Remap::Sequence(keys)) => {
+                                if event_type == CGEventType::KeyDown {
+                                    debug!("Expanding {} into a {}-key sequence", keycode, keys.len());
+                                    post_sequence(keys);
+                                }
+                                // Swallow the original keystroke: a passed-through `None` would
+                                // forward it unchanged in addition to the synthesized sequence.
+                                event.set_type(CGEventType::Null);
+                                return Some(event.clone());
+                            }
+                            None => {}
                         }
                     } else {
                         debug!("Did not remap {}, no modifier keys pressed", keycode);
                     }
                 }
+                CGEventType::FlagsChanged => {
+                    let keycode =
+                        event.get_integer_value_field(EventField::KEYBOARD_EVENT_KEYCODE) as u64;
+                    if keymaps().switch_trigger_keycode == Some(keycode) {
+                        // A modifier trigger has no KeyDown/KeyUp; use its device mask bit to
+                        // tell a press (bit set) from a release (bit clear) instead.
+                        match keycodes::device_mask_for_modifier(keycode) {
+                            Some(mask) => {
+                                if event.get_flags().bits() & mask != 0 {
+                                    switch_to_next_profile();
+                                }
+                            }
+                            None => {
+                                warn!(
+                                    keycode,
+                                    "switch_trigger_keycode has no left/right press state \
+                                     (CapsLock/Function only report a toggle), so it can \
+                                     never trigger a switch; the key is still being swallowed"
+                                );
+                            }
+                        }
+                        event.set_type(CGEventType::Null);
+                        return Some(event.clone());
+                    }
+                    let mapped = match lookup_remap(keycode) {
+                        Some(config::Remap::Key(mapped)) => Some(*mapped),
+                        _ => None,
+                    };
+                    if let Some(mapped) = mapped {
+                        match (
+                            keycodes::device_mask_for_modifier(keycode),
+                            keycodes::device_mask_for_modifier(mapped),
+                        ) {
+                            (Some(from_mask), Some(to_mask)) => {
+                                let raw_flags = event.get_flags().bits();
+                                let pressed = raw_flags & from_mask != 0;
+                                let mut new_flags = raw_flags & !from_mask;
+                                if pressed {
+                                    new_flags |= to_mask;
+                                } else {
+                                    new_flags &= !to_mask;
+                                }
+                                // The device mask bits above aren't what apps (or our own
+                                // gate at the top of this closure) actually check — flip the
+                                // canonical kCGEventFlagMask{Control,Shift,Command,Alternate}
+                                // bit for each family too, only touching the source family's
+                                // bit if the other side of that pair isn't also still held.
+                                if let (Some(from_canonical), Some(to_canonical)) = (
+                                    keycodes::canonical_flag_for_modifier(keycode),
+                                    keycodes::canonical_flag_for_modifier(mapped),
+                                ) {
+                                    let from_sibling_held =
+                                        keycodes::sibling_device_mask(keycode)
+                                            .is_some_and(|m| raw_flags & m != 0);
+                                    let mut flags = CGEventFlags::from_bits_truncate(new_flags);
+                                    if !from_sibling_held {
+                                        flags.remove(from_canonical);
+                                    }
+                                    if pressed {
+                                        flags.insert(to_canonical);
+                                    } else {
+                                        let to_sibling_held =
+                                            keycodes::sibling_device_mask(mapped)
+                                                .is_some_and(|m| new_flags & m != 0);
+                                        if !to_sibling_held {
+                                            flags.remove(to_canonical);
+                                        }
+                                    }
+                                    new_flags = flags.bits();
+                                }
+                                debug!("Remapped modifier {} to {}", keycode, mapped);
+                                event.set_integer_value_field(
+                                    EventField::KEYBOARD_EVENT_KEYCODE,
+                                    mapped as i64,
+                                );
+                                event.set_flags(CGEventFlags::from_bits_truncate(new_flags));
+                                return Some(event.clone());
+                            }
+                            _ if keycodes::is_modifier_keycode(keycode)
+                                || keycodes::is_modifier_keycode(mapped) =>
+                            {
+                                warn!(
+                                    from = keycode,
+                                    to = mapped,
+                                    "Modifier remap unsupported (CapsLock/Function have no \
+                                     left/right press state); ignoring"
+                                );
+                            }
+                            _ => {}
+                        }
+                    }
+                }
                 CGEventType::TapDisabledByTimeout | CGEventType::TapDisabledByUserInput => {
                     error!("Event tap disabled; cause: {:?}", event_type);
                 }
@@ -351,3 +495,39 @@ fn run_tap() -> ! {
     CFRunLoop::run_current();
     process::exit(0);
 }
+
+/// Run a read-only event tap that logs every keypress's (and modifier's) symbolic name and
+/// raw keycode, without remapping anything. Lets users discover what keycode a physical key
+/// produces, for building a `keymaps.toml`.
+fn run_dump_keys() -> ! {
+    let tap = CGEventTap::new(
+        CGEventTapLocation::AnnotatedSession,
+        CGEventTapPlacement::HeadInsertEventTap,
+        CGEventTapOptions::ListenOnly,
+        vec![CGEventType::KeyDown, CGEventType::FlagsChanged],
+        |_, event_type, event| {
+            let keycode = event.get_integer_value_field(EventField::KEYBOARD_EVENT_KEYCODE) as u64;
+            let label = if event_type == CGEventType::FlagsChanged {
+                "MODIFIER"
+            } else {
+                "KEY"
+            };
+            match keycodes::name_for_keycode(keycode) {
+                Some(name) => info!("{} {} ({})", label, keycode, name),
+                None => info!("{} {} (unknown)", label, keycode),
+            }
+            None
+        },
+    )
+    .expect("Failed to create event tap. Make sure to grant accessibility permissions.");
+
+    let run_loop = CFRunLoop::get_current();
+    let source = tap
+        .mach_port
+        .create_runloop_source(0)
+        .expect("Failed to create run loop source");
+    unsafe { run_loop.add_source(&source, kCFRunLoopCommonModes) };
+    tap.enable();
+    CFRunLoop::run_current();
+    process::exit(0);
+}